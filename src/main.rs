@@ -1,11 +1,17 @@
-use chrono::{Days, NaiveTime, TimeDelta, Timelike};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Timelike};
 use clap::Parser;
+use rusqlite::OptionalExtension;
 use rusqlite::Row;
 use rusqlite::config::DbConfig;
 
 const HOME_DIR: &'static str = "tracc";
 const DATABASE_FILE: &'static str = "db.sqlite";
 const DT_FMT: &'static str = "%H:%M %d.%m.%y";
+const DATE_FMT: &'static str = "%d.%m.%y";
+const DEFAULT_SHEET: &'static str = "default";
 
 type LocalDT = chrono::DateTime<chrono::Local>;
 
@@ -50,38 +56,164 @@ fn get_database_connection() -> Result<rusqlite::Connection, String> {
     Ok(conn)
 }
 
-struct App {
+/// A single step in the migration ladder. The step at index N in [`MIGRATIONS`]
+/// takes a database from `user_version = N` to `user_version = N + 1`, e.g. the
+/// step at index 0 takes a fresh database to `user_version = 1`.
+enum Migration {
+    Sql(&'static str),
+    /// Scaffolding for a future migration step that can't be expressed as a
+    /// single SQL statement (e.g. backfilling a column with computed data).
+    /// Unused for now.
+    #[allow(dead_code)]
+    Code(fn(&rusqlite::Connection) -> Result<(), String>),
+}
+
+/// Ordered list of migrations. Never reorder or remove an entry here: a
+/// user's `db.sqlite` remembers how far down this list it has already been
+/// taken via `PRAGMA user_version`, and applying a step out of order would
+/// silently corrupt existing installs.
+const MIGRATIONS: &[Migration] = &[
+    Migration::Sql(
+        "create table if not exists entries (
+id INTEGER,
+datetime INTEGER,
+kind INTEGER,
+PRIMARY KEY(id)
+);",
+    ),
+    Migration::Sql(
+        "alter table entries add column sheet TEXT NOT NULL DEFAULT 'default';
+create table meta (
+key TEXT,
+value TEXT,
+PRIMARY KEY(key)
+);",
+    ),
+    Migration::Sql("alter table entries add column note TEXT;"),
+];
+
+/// Bring `conn` up to `MIGRATIONS.len()`, applying whatever steps are missing.
+/// Each step runs inside its own transaction, and `user_version` is bumped in
+/// that same transaction, so a failure partway through
+/// leaves the database at the last fully-applied version instead of a torn
+/// state.
+fn run_migrations(conn: &rusqlite::Connection) -> Result<(), String> {
+    let current_version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|err| format!("Could not read schema version: {err}"))?;
+    let current_version = current_version as usize;
+
+    if current_version > MIGRATIONS.len() {
+        return Err(format!(
+            "Database schema version {current_version} is newer than this binary understands (knows up to {}). Refusing to touch it; please update tracc.",
+            MIGRATIONS.len()
+        ));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current_version) {
+        let target_version = index + 1;
+
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start migration transaction: {err}"))?;
+
+        match migration {
+            Migration::Sql(sql) => {
+                tx.execute_batch(sql)
+                    .map_err(|err| format!("Migration {target_version} failed: {err}"))?;
+            }
+            Migration::Code(step) => {
+                step(&tx)?;
+            }
+        }
+
+        tx.pragma_update(None, "user_version", target_version)
+            .map_err(|err| format!("Could not bump schema version to {target_version}: {err}"))?;
+
+        tx.commit()
+            .map_err(|err| format!("Could not commit migration {target_version}: {err}"))?;
+    }
+
+    Ok(())
+}
+
+/// Read the currently active sheet from the `meta` table, falling back to
+/// [`DEFAULT_SHEET`] if none has been selected yet (e.g. on a fresh install).
+fn get_active_sheet(conn: &rusqlite::Connection) -> Result<String, String> {
+    conn.query_row(
+        "SELECT value FROM meta WHERE key = 'active_sheet'",
+        (),
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|err| format!("Could not read active sheet: {err}"))
+    .map(|value| value.unwrap_or_else(|| DEFAULT_SHEET.to_string()))
+}
+
+/// The facts of the world an [`App`] acts on: the database connection and
+/// "now". Bundling them together (rather than hardcoding `chrono::Local::now()`
+/// at every use site) lets callers override `now`, which is what makes `--at`
+/// backdating possible and the duration math in [`sum_periods`]
+/// testable against a fixed instant.
+struct Facts {
     conn: rusqlite::Connection,
     now: LocalDT,
 }
 
-impl App {
-    pub fn try_init() -> Result<Self, String> {
+impl Facts {
+    pub fn try_init(now: LocalDT) -> Result<Self, String> {
         let conn = get_database_connection()?;
-        let now = chrono::Local::now();
 
-        let _ = conn
+        run_migrations(&conn)?;
+
+        Ok(Facts { conn, now })
+    }
+}
+
+struct App {
+    facts: Facts,
+    active_sheet: String,
+}
+
+impl App {
+    pub fn try_init(now: LocalDT) -> Result<Self, String> {
+        let facts = Facts::try_init(now)?;
+        let active_sheet = get_active_sheet(&facts.conn)?;
+
+        Ok(App {
+            facts,
+            active_sheet,
+        })
+    }
+
+    /// Switch the active sheet, creating it implicitly: sheets aren't tracked
+    /// in their own table, they simply come into existence the first time an
+    /// entry is tagged with their name.
+    pub fn switch_sheet(&mut self, name: &str) -> Result<(), String> {
+        self.facts.conn
             .execute(
-                "create table if not exists entries (
-id INTEGER, 
-datetime INTEGER,
-kind INTEGER,
-PRIMARY KEY(id)
-);",
-                (),
+                "INSERT INTO meta (key, value) VALUES ('active_sheet', ?1)
+ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                (name,),
             )
-            .map_err(|err| format!("Could not create entries table: {err}"))?;
+            .map_err(|err| format!("Could not switch active sheet: {err}"))?;
 
-        Ok(App { conn, now })
+        println!("Switched active sheet to '{name}'.");
+        self.active_sheet = name.to_string();
+
+        Ok(())
     }
 
-    pub fn get_last_entry(&self) -> Result<Option<Entry>, String> {
+    pub fn get_last_entry(&self, sheet: &str) -> Result<Option<Entry>, String> {
         let mut statement = self
+            .facts
             .conn
-            .prepare("SELECT * FROM entries where datetime = (SELECT MAX(datetime) from entries);")
+            .prepare(
+                "SELECT * FROM entries where sheet = ?1 and datetime = (SELECT MAX(datetime) from entries where sheet = ?1);",
+            )
             .map_err(|err| format!("Could not prepare statement: {err}"))?;
         let mut rows = statement
-            .query(())
+            .query((sheet,))
             .map_err(|err| format!("Could not get last entry: {err}"))?;
 
         let last_entry = match rows.next() {
@@ -101,155 +233,987 @@ PRIMARY KEY(id)
         Ok(Some(last_entry))
     }
 
+    /// The timestamp a sheet's history is free to be appended after, for
+    /// imports: errors if the sheet has a period still running, since that
+    /// can't be reconciled with an appended period no matter its timing.
+    fn last_known_sheet_end(&self, sheet: &str) -> Result<Option<LocalDT>, String> {
+        match self.get_last_entry(sheet)? {
+            Some(Entry::Begin { datetime, .. }) => Err(format!(
+                "Cannot import into sheet '{sheet}': it has a period still running since {}.",
+                datetime.format(DT_FMT)
+            )),
+            Some(Entry::End { datetime, .. }) => Ok(Some(datetime)),
+            None => Ok(None),
+        }
+    }
+
     pub fn add_begin(&mut self) -> Result<(), String> {
-        let last_entry = self.get_last_entry()?;
+        let sheet = self.active_sheet.clone();
+        let last_entry = self.get_last_entry(&sheet)?;
 
         match last_entry {
-            Some(Entry::Begin(date_time)) => {
+            Some(Entry::Begin { datetime, .. }) => {
                 return Err(format!(
-                    "Cannot start period. Current period started at {} is still running.",
-                    date_time.format(DT_FMT)
+                    "Cannot start period. Current period on sheet '{sheet}' started at {} is still running.",
+                    datetime.format(DT_FMT)
                 ));
             }
-            Some(Entry::End(date_time)) => {
+            Some(Entry::End { datetime, .. }) => {
+                if self.facts.now < datetime {
+                    return Err(format!(
+                        "Cannot start period at {}: it is earlier than the previous period on sheet '{sheet}', which ended at {}.",
+                        self.facts.now.format(DT_FMT),
+                        datetime.format(DT_FMT)
+                    ));
+                }
                 println!(
-                    "Starting new period. Last one ended at {}",
-                    date_time.format(DT_FMT)
+                    "Starting new period on sheet '{sheet}'. Last one ended at {}",
+                    datetime.format(DT_FMT)
                 )
             }
             None => {
-                println!("Starting new period.",)
+                println!("Starting new period on sheet '{sheet}'.")
             }
         }
 
-        self.conn
+        self.facts
+            .conn
             .execute(
-                "INSERT INTO entries (datetime, kind) VALUES (?1, 0)",
-                (self.now.timestamp(),),
+                "INSERT INTO entries (datetime, kind, sheet) VALUES (?1, 0, ?2)",
+                (self.facts.now.timestamp(), &sheet),
             )
             .map_err(|err| format!("Could not insert new begin entry: {err}"))?;
 
         Ok(())
     }
 
-    pub fn add_end(&mut self) -> Result<(), String> {
-        let last_entry = self.get_last_entry()?;
+    pub fn add_end(&mut self, note: Option<String>) -> Result<(), String> {
+        let sheet = self.active_sheet.clone();
+        let last_entry = self.get_last_entry(&sheet)?;
 
         match last_entry {
-            Some(Entry::Begin(date_time)) => {
-                println!("Ending period started at {}", date_time.format(DT_FMT))
+            Some(Entry::Begin { datetime, .. }) => {
+                if self.facts.now < datetime {
+                    return Err(format!(
+                        "Cannot end period at {}: it is earlier than its own begin on sheet '{sheet}', which started at {}.",
+                        self.facts.now.format(DT_FMT),
+                        datetime.format(DT_FMT)
+                    ));
+                }
+                println!(
+                    "Ending period on sheet '{sheet}' started at {}",
+                    datetime.format(DT_FMT)
+                )
             }
-            Some(Entry::End(date_time)) => {
+            Some(Entry::End { datetime, .. }) => {
                 return Err(format!(
-                    "Cannot end period. Last period has already been ended at {}.",
-                    date_time.format(DT_FMT)
+                    "Cannot end period. Last period on sheet '{sheet}' has already been ended at {}.",
+                    datetime.format(DT_FMT)
                 ));
             }
-            None => return Err(format!("Cannot insert end entry as first entry")),
+            None => return Err(format!("Cannot insert end entry as first entry on sheet '{sheet}'")),
         }
 
-        self.conn
+        self.facts
+            .conn
             .execute(
-                "INSERT INTO entries (datetime, kind) VALUES (?1, 1)",
-                (self.now.timestamp(),),
+                "INSERT INTO entries (datetime, kind, sheet, note) VALUES (?1, 1, ?2, ?3)",
+                (self.facts.now.timestamp(), &sheet, &note),
             )
             .map_err(|err| format!("Could not insert new end entry: {err}"))?;
 
         Ok(())
     }
 
-    fn show(&self) -> Result<(), String> {
-        let mut query = self
+    /// Attach a note to the currently open period on the active sheet, e.g.
+    /// `tracc note "fixed parser"` right before calling it a day.
+    pub fn annotate_open_period(&mut self, note: &str) -> Result<(), String> {
+        let sheet = self.active_sheet.clone();
+        let last_entry = self.get_last_entry(&sheet)?;
+
+        let id = match last_entry {
+            Some(Entry::Begin { id, .. }) => id,
+            Some(Entry::End { .. }) | None => {
+                return Err(format!("No open period on sheet '{sheet}' to attach a note to."));
+            }
+        };
+
+        self.facts
             .conn
-            .prepare("SELECT * FROM entries order by datetime;")
-            .map_err(|err| format!("Could prepare entries query: {err}"))?;
-        let mut entries = query
-            .query(())
-            .map_err(|err| format!("Could not query entries: {err}"))?;
+            .execute("UPDATE entries SET note = ?1 WHERE id = ?2", (note, id))
+            .map_err(|err| format!("Could not set note on entry {id}: {err}"))?;
+
+        println!("Noted on the period running on sheet '{sheet}'.");
+
+        Ok(())
+    }
+
+    fn get_entry_by_id(&self, id: i64) -> Result<Option<Entry>, String> {
+        let mut statement = self
+            .facts
+            .conn
+            .prepare("SELECT * FROM entries where id = ?1;")
+            .map_err(|err| format!("Could not prepare statement: {err}"))?;
+        let mut rows = statement
+            .query((id,))
+            .map_err(|err| format!("Could not get entry {id}: {err}"))?;
+
+        match rows.next() {
+            Ok(Some(row)) => Ok(Some(Entry::from_db_row(row)?)),
+            Ok(None) => Ok(None),
+            Err(err) => Err(format!("Could not get entry {id}: {err}")),
+        }
+    }
+
+    /// Rewrite an entry's datetime and/or note. At least one of `datetime`
+    /// (parsed with [`DT_FMT`]) or `note` must be given.
+    pub fn edit(
+        &mut self,
+        id: i64,
+        datetime: Option<String>,
+        note: Option<String>,
+    ) -> Result<(), String> {
+        if self.get_entry_by_id(id)?.is_none() {
+            return Err(format!("No entry with id {id}."));
+        }
 
-        while let Ok(Some(row)) = entries.next() {
-            let entry = Entry::from_db_row(row)?;
+        if datetime.is_none() && note.is_none() {
+            return Err(format!(
+                "Nothing to edit entry {id} with. Pass --datetime and/or --note."
+            ));
+        }
+
+        if let Some(raw) = &datetime {
+            let parsed = parse_at(raw)?;
+
+            let entry = self.get_entry_by_id(id)?.expect("checked above");
+            let sheet_entries = self.query_sheet_entries(entry.sheet())?;
+            let position = sheet_entries
+                .iter()
+                .position(|e| e.id() == id)
+                .expect("entry is in its own sheet's entries");
 
-            match entry {
-                Entry::Begin(dt) => {
-                    println!("BEGIN: {}", dt.format(DT_FMT));
+            if let Some(prev) = position.checked_sub(1).and_then(|i| sheet_entries.get(i)) {
+                if parsed <= prev.datetime() {
+                    return Err(format!(
+                        "Cannot move entry {id} to {}: it would no longer be after entry {} on sheet '{}', which is at {}.",
+                        parsed.format(DT_FMT),
+                        prev.id(),
+                        entry.sheet(),
+                        prev.datetime().format(DT_FMT)
+                    ));
                 }
-                Entry::End(dt) => {
-                    println!("END:   {}", dt.format(DT_FMT));
+            }
+            if let Some(next) = sheet_entries.get(position + 1) {
+                if parsed >= next.datetime() {
+                    return Err(format!(
+                        "Cannot move entry {id} to {}: it would no longer be before entry {} on sheet '{}', which is at {}.",
+                        parsed.format(DT_FMT),
+                        next.id(),
+                        entry.sheet(),
+                        next.datetime().format(DT_FMT)
+                    ));
                 }
             }
+
+            self.facts
+                .conn
+                .execute(
+                    "UPDATE entries SET datetime = ?1 WHERE id = ?2",
+                    (parsed.timestamp(), id),
+                )
+                .map_err(|err| format!("Could not update entry {id}: {err}"))?;
+        }
+
+        if let Some(note) = &note {
+            self.facts
+                .conn
+                .execute("UPDATE entries SET note = ?1 WHERE id = ?2", (note, id))
+                .map_err(|err| format!("Could not update entry {id}: {err}"))?;
         }
 
+        println!("Updated entry {id}.");
+
         Ok(())
     }
 
-    fn today(&self) -> Result<(), String> {
-        let today_start = self
-            .now
-            .with_time(NaiveTime::from_hms_opt(0, 0, 0).expect("is valid"))
-            .unwrap();
+    /// Delete a single entry. Since `today()`/reporting rely on every Begin
+    /// having a matching End on the same sheet, warn (but still proceed) when
+    /// this would leave the other half of a pair dangling.
+    pub fn delete(&mut self, id: i64) -> Result<(), String> {
+        let entry = self
+            .get_entry_by_id(id)?
+            .ok_or_else(|| format!("No entry with id {id}."))?;
 
-        let today_end = today_start
-            .checked_add_days(Days::new(1))
-            .expect("is inside of range");
+        let sheet_entries = self.query_sheet_entries(entry.sheet())?;
+        let position = sheet_entries.iter().position(|e| e.id() == id);
+        let orphan = match (&entry, position) {
+            (Entry::Begin { .. }, Some(position)) => sheet_entries.get(position + 1),
+            (Entry::End { .. }, Some(position)) => {
+                position.checked_sub(1).and_then(|i| sheet_entries.get(i))
+            }
+            _ => None,
+        };
+
+        if let Some(orphan) = orphan {
+            println!(
+                "Warning: entry {} on sheet '{}' will be left without its matching half.",
+                orphan.id(),
+                entry.sheet()
+            );
+        }
+
+        self.facts
+            .conn
+            .execute("DELETE FROM entries where id = ?1", (id,))
+            .map_err(|err| format!("Could not delete entry {id}: {err}"))?;
+
+        println!("Deleted entry {id}.");
+
+        Ok(())
+    }
+
+    fn query_all_entries(&self) -> Result<Vec<Entry>, String> {
+        let mut query = self
+            .facts
+            .conn
+            .prepare("SELECT * FROM entries order by datetime;")
+            .map_err(|err| format!("Could prepare entries query: {err}"))?;
+        let mut rows = query
+            .query(())
+            .map_err(|err| format!("Could not query entries: {err}"))?;
+
+        let mut entries = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            entries.push(Entry::from_db_row(row)?);
+        }
+        Ok(entries)
+    }
+
+    fn query_sheet_entries(&self, sheet: &str) -> Result<Vec<Entry>, String> {
+        let mut query = self
+            .facts
+            .conn
+            .prepare("SELECT * FROM entries where sheet = ?1 order by datetime;")
+            .map_err(|err| format!("Could prepare entries query: {err}"))?;
+        let mut rows = query
+            .query((sheet,))
+            .map_err(|err| format!("Could not query entries: {err}"))?;
+
+        let mut entries = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            entries.push(Entry::from_db_row(row)?);
+        }
+        Ok(entries)
+    }
 
+    fn query_entries_in_range(&self, start: i64, end: i64) -> Result<Vec<Entry>, String> {
         let mut query = self
+            .facts
             .conn
             .prepare(
                 "SELECT * FROM entries where datetime >= ?1 and datetime < ?2 order by datetime;",
             )
             .map_err(|err| format!("Could prepare entries query: {err}"))?;
+        let mut rows = query
+            .query((start, end))
+            .map_err(|err| format!("Could not query entries: {err}"))?;
+
+        let mut entries = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            entries.push(Entry::from_db_row(row)?);
+        }
+        Ok(entries)
+    }
 
-        let mut entries = query
-            .query((today_start.timestamp(), today_end.timestamp()))
+    fn query_sheet_entries_in_range(
+        &self,
+        start: i64,
+        end: i64,
+        sheet: &str,
+    ) -> Result<Vec<Entry>, String> {
+        let mut query = self
+            .facts
+            .conn
+            .prepare(
+                "SELECT * FROM entries where sheet = ?1 and datetime >= ?2 and datetime < ?3 order by datetime;",
+            )
+            .map_err(|err| format!("Could prepare entries query: {err}"))?;
+        let mut rows = query
+            .query((sheet, start, end))
             .map_err(|err| format!("Could not query entries: {err}"))?;
 
-        let mut time = TimeDelta::zero();
+        let mut entries = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            entries.push(Entry::from_db_row(row)?);
+        }
+        Ok(entries)
+    }
+
+    /// If a period on `sheet` was already running before `start`, fetch its
+    /// Begin. Report commands prepend this to their windowed entries so a
+    /// period spanning the window boundary still pairs correctly instead of
+    /// looking like a corrupted End-without-Begin.
+    fn open_begin_before(&self, sheet: &str, start: i64) -> Result<Option<Entry>, String> {
+        let mut query = self
+            .facts
+            .conn
+            .prepare(
+                "SELECT * FROM entries where sheet = ?1 and datetime < ?2 order by datetime desc limit 1;",
+            )
+            .map_err(|err| format!("Could not prepare statement: {err}"))?;
+        let mut rows = query
+            .query((sheet, start))
+            .map_err(|err| format!("Could not get entry before window: {err}"))?;
+
+        match rows.next() {
+            Ok(Some(row)) => match Entry::from_db_row(row)? {
+                entry @ Entry::Begin { .. } => Ok(Some(entry)),
+                Entry::End { .. } => Ok(None),
+            },
+            Ok(None) => Ok(None),
+            Err(err) => Err(format!("Could not get entry before window: {err}")),
+        }
+    }
+
+    /// Like [`App::query_sheet_entries_in_range`], but for report commands:
+    /// prepends the sheet's still-open Begin from before `start` (if any),
+    /// so [`sum_periods`] sees a matched pair instead of a dangling End.
+    fn report_sheet_entries(&self, sheet: &str, start: i64, end: i64) -> Result<Vec<Entry>, String> {
+        let mut entries = Vec::new();
+        if let Some(begin) = self.open_begin_before(sheet, start)? {
+            entries.push(begin);
+        }
+        entries.extend(self.query_sheet_entries_in_range(start, end, sheet)?);
+        Ok(entries)
+    }
+
+    /// Like [`App::report_sheet_entries`], but across every sheet.
+    fn report_entries(&self, start: i64, end: i64) -> Result<Vec<Entry>, String> {
+        let mut entries = Vec::new();
+
+        let mut sheets_query = self
+            .facts
+            .conn
+            .prepare("SELECT DISTINCT sheet FROM entries;")
+            .map_err(|err| format!("Could not prepare sheet query: {err}"))?;
+        let mut sheet_rows = sheets_query
+            .query(())
+            .map_err(|err| format!("Could not query sheets: {err}"))?;
+        while let Ok(Some(row)) = sheet_rows.next() {
+            let sheet: String = row
+                .get("sheet")
+                .map_err(|err| format!("Could not get sheet from row: {err}"))?;
+            if let Some(begin) = self.open_begin_before(&sheet, start)? {
+                entries.push(begin);
+            }
+        }
+
+        entries.extend(self.query_entries_in_range(start, end)?);
+        Ok(entries)
+    }
+
+    fn print_entries(entries: &[Entry]) {
+        for entry in entries {
+            let kind = match entry {
+                Entry::Begin { .. } => "BEGIN",
+                Entry::End { .. } => "END  ",
+            };
+            print!(
+                "[{}] {kind}: {}",
+                entry.id(),
+                entry.datetime().format(DT_FMT)
+            );
+            if let Some(note) = entry.note() {
+                print!(" - {note}");
+            }
+            println!();
+        }
+    }
+
+    fn show(&self, sheet: Option<&str>) -> Result<(), String> {
+        match sheet {
+            Some(sheet) => {
+                let entries = self.query_sheet_entries(sheet)?;
+                Self::print_entries(&entries);
+            }
+            None => {
+                let entries = self.query_all_entries()?;
+                let by_sheet = group_by_sheet(entries);
+                for (sheet, entries) in &by_sheet {
+                    println!("== {sheet} ==");
+                    Self::print_entries(entries);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn today(&self, sheet: Option<&str>) -> Result<(), String> {
+        let today_start = day_start(self.facts.now);
+        let today_end = today_start
+            .checked_add_days(Days::new(1))
+            .expect("is inside of range");
 
-        let mut current_slot_begin = None;
-        while let Ok(Some(row)) = entries.next() {
-            let entry = Entry::from_db_row(row)?;
+        match sheet {
+            Some(sheet) => {
+                let entries = self.report_sheet_entries(
+                    sheet,
+                    today_start.timestamp(),
+                    today_end.timestamp(),
+                )?;
+                let by_day = sum_periods(&entries, self.facts.now)?;
+                print_total(
+                    &format!("Total time spent today on '{sheet}'"),
+                    total_time(&by_day),
+                );
+            }
+            None => {
+                let entries =
+                    self.report_entries(today_start.timestamp(), today_end.timestamp())?;
+                let by_sheet = group_by_sheet(entries);
 
-            match entry {
-                Entry::Begin(dt) => {
-                    current_slot_begin = Some(dt)
+                let mut grand_total = TimeDelta::zero();
+                for (sheet, entries) in &by_sheet {
+                    let time = total_time(&sum_periods(entries, self.facts.now)?);
+                    grand_total += time;
+                    print_total(&format!("  {sheet}"), time);
                 }
-                Entry::End(dt) => {
-                    if let Some(begin) = current_slot_begin {
-                        time += dt - begin;
-                        current_slot_begin = None;
-                    } else {
-                        return Err(format!(
-                            "Corrupted database. End at {} without previous period begin.",
-                            dt.format(DT_FMT)
-                        ));
-                    }
+                print_total("Total time spent today", grand_total);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shared backbone of `week`/`month`/`range`: total the `[start, end)`
+    /// window per calendar day, merging across sheets when none is given.
+    fn report(
+        &self,
+        label: &str,
+        start: LocalDT,
+        end: LocalDT,
+        sheet: Option<&str>,
+    ) -> Result<(), String> {
+        // A period still open at the end of the window is counted up to the
+        // window's end at most, not up to the present.
+        let now = self.facts.now.min(end);
+        let by_day = match sheet {
+            Some(sheet) => {
+                let entries =
+                    self.report_sheet_entries(sheet, start.timestamp(), end.timestamp())?;
+                sum_periods(&entries, now)?
+            }
+            None => {
+                let entries = self.report_entries(start.timestamp(), end.timestamp())?;
+                let by_sheet = group_by_sheet(entries);
+
+                let mut per_sheet_days = Vec::new();
+                for (_, entries) in &by_sheet {
+                    per_sheet_days.push(sum_periods(entries, now)?);
                 }
+                merge_day_totals(per_sheet_days)
+            }
+        };
+
+        print_day_totals(label, &by_day);
+
+        Ok(())
+    }
+
+    fn week(&self, sheet: Option<&str>) -> Result<(), String> {
+        let week_start = day_start(self.facts.now)
+            .checked_sub_days(Days::new(self.facts.now.weekday().num_days_from_monday() as u64))
+            .expect("is inside of range");
+        let week_end = week_start
+            .checked_add_days(Days::new(7))
+            .expect("is inside of range");
+
+        self.report("Total time this week", week_start, week_end, sheet)
+    }
+
+    fn month(&self, sheet: Option<&str>) -> Result<(), String> {
+        let month_start = day_start(
+            self.facts
+                .now
+                .with_day(1)
+                .expect("the first day of any month is valid"),
+        );
+        let month_end = month_start
+            .checked_add_months(Months::new(1))
+            .expect("is inside of range");
+
+        self.report("Total time this month", month_start, month_end, sheet)
+    }
+
+    fn range(&self, from: &str, to: &str, sheet: Option<&str>) -> Result<(), String> {
+        let from_date = NaiveDate::parse_from_str(from, DATE_FMT)
+            .map_err(|err| format!("Could not parse --from \"{from}\" as \"{DATE_FMT}\": {err}"))?;
+        let to_date = NaiveDate::parse_from_str(to, DATE_FMT)
+            .map_err(|err| format!("Could not parse --to \"{to}\" as \"{DATE_FMT}\": {err}"))?;
+
+        let range_start = localize_date(from_date)?;
+        let range_end = localize_date(to_date)?
+            .checked_add_days(Days::new(1))
+            .expect("is inside of range");
+
+        self.report("Total time in range", range_start, range_end, sheet)
+    }
+
+    /// Stream all entries, optionally restricted to a `--from`/`--to` date
+    /// range, to stdout for backup or feeding other tooling.
+    fn export(
+        &self,
+        format: ExportFormat,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<(), String> {
+        let entries = match (from, to) {
+            (Some(from), Some(to)) => {
+                let from_date = NaiveDate::parse_from_str(from, DATE_FMT).map_err(|err| {
+                    format!("Could not parse --from \"{from}\" as \"{DATE_FMT}\": {err}")
+                })?;
+                let to_date = NaiveDate::parse_from_str(to, DATE_FMT).map_err(|err| {
+                    format!("Could not parse --to \"{to}\" as \"{DATE_FMT}\": {err}")
+                })?;
+
+                let start = localize_date(from_date)?;
+                let end = localize_date(to_date)?
+                    .checked_add_days(Days::new(1))
+                    .expect("is inside of range");
+
+                self.query_entries_in_range(start.timestamp(), end.timestamp())?
             }
+            (None, None) => self.query_all_entries()?,
+            _ => return Err(format!("--from and --to must be given together.")),
+        };
+
+        match format {
+            ExportFormat::Csv => export_csv(&entries),
+            ExportFormat::Json => export_json(&entries),
+        }
+
+        Ok(())
+    }
+
+    pub fn import(&mut self, from: &Path, format: ImportFormat) -> Result<(), String> {
+        match format {
+            ImportFormat::Timetrap => self.import_timetrap(from),
         }
-        if let Some(begin) = current_slot_begin {
-            time += self.now - begin;
+    }
+
+    fn import_timetrap(&mut self, from: &Path) -> Result<(), String> {
+        let source = rusqlite::Connection::open_with_flags(
+            from,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .map_err(|err| format!("Could not open timetrap database at {}: {err}", from.display()))?;
+
+        let mut statement = source
+            .prepare("SELECT sheet, note, start, end FROM entries ORDER BY start;")
+            .map_err(|err| format!("Could not prepare timetrap query: {err}"))?;
+        let mut rows = statement
+            .query(())
+            .map_err(|err| format!("Could not read timetrap entries: {err}"))?;
+
+        let mut periods = Vec::new();
+        while let Some(row) = rows
+            .next()
+            .map_err(|err| format!("Could not read timetrap entry: {err}"))?
+        {
+            let sheet: String = row
+                .get("sheet")
+                .map_err(|err| format!("Could not get sheet from timetrap row: {err}"))?;
+            let note: Option<String> = row
+                .get("note")
+                .map_err(|err| format!("Could not get note from timetrap row: {err}"))?;
+            let start_raw: String = row
+                .get("start")
+                .map_err(|err| format!("Could not get start from timetrap row: {err}"))?;
+            let end_raw: Option<String> = row
+                .get("end")
+                .map_err(|err| format!("Could not get end from timetrap row: {err}"))?;
+
+            let start = parse_timetrap_dt(&start_raw)?;
+            let end = end_raw.as_deref().map(parse_timetrap_dt).transpose()?;
+
+            if let Some(end) = end {
+                if end < start {
+                    return Err(format!(
+                        "Malformed timetrap entry on sheet '{sheet}': end {} is earlier than start {}.",
+                        end.format(DT_FMT),
+                        start.format(DT_FMT)
+                    ));
+                }
+            }
+
+            periods.push((sheet, note, start, end));
         }
-        if time.num_days() != 0 {
-            return Err(format!("Error with timedelta calculation. Number of days cannot be greater than 0. this must be a database corruption issue."));
+
+        let tx = self
+            .facts
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start import transaction: {err}"))?;
+
+        let mut last_end_per_sheet: HashMap<String, LocalDT> = HashMap::new();
+        for (sheet, note, start, end) in &periods {
+            if !last_end_per_sheet.contains_key(sheet) {
+                if let Some(existing_end) = self.last_known_sheet_end(sheet)? {
+                    last_end_per_sheet.insert(sheet.clone(), existing_end);
+                }
+            }
+
+            if let Some(last_end) = last_end_per_sheet.get(sheet) {
+                if start < last_end {
+                    return Err(format!(
+                        "Malformed timetrap data: entry on sheet '{sheet}' starts at {} before the previous one on that sheet ended at {}.",
+                        start.format(DT_FMT),
+                        last_end.format(DT_FMT)
+                    ));
+                }
+            }
+            last_end_per_sheet.insert(sheet.clone(), end.unwrap_or(*start));
+
+            let (begin_note, end_note) = match end {
+                Some(_) => (None, note.as_deref()),
+                None => (note.as_deref(), None),
+            };
+
+            tx.execute(
+                "INSERT INTO entries (datetime, kind, sheet, note) VALUES (?1, 0, ?2, ?3)",
+                (start.timestamp(), sheet, begin_note),
+            )
+            .map_err(|err| format!("Could not insert imported begin entry: {err}"))?;
+
+            if let Some(end) = end {
+                tx.execute(
+                    "INSERT INTO entries (datetime, kind, sheet, note) VALUES (?1, 1, ?2, ?3)",
+                    (end.timestamp(), sheet, end_note),
+                )
+                .map_err(|err| format!("Could not insert imported end entry: {err}"))?;
+            }
         }
-        println!("Total time spent today: {:2}:{:02}", time.num_hours(), time.num_minutes() - time.num_hours() * 60);
+
+        tx.commit()
+            .map_err(|err| format!("Could not commit import: {err}"))?;
+
+        println!("Imported {} period(s) from {}.", periods.len(), from.display());
 
         Ok(())
     }
 }
 
+/// Group entries by the sheet they belong to, preserving chronological order
+/// within each sheet and ordering the sheets themselves alphabetically.
+fn group_by_sheet(entries: Vec<Entry>) -> BTreeMap<String, Vec<Entry>> {
+    let mut by_sheet: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    for entry in entries {
+        by_sheet
+            .entry(entry.sheet().to_string())
+            .or_default()
+            .push(entry);
+    }
+    by_sheet
+}
+
+/// Walk a sheet's chronologically ordered Begin/End entries and bucket the
+/// covered duration per calendar day (the day a period started on), counting
+/// a still-open period as running until `now`. This is the one place every
+/// reporting command (`today`, `week`, `month`, `range`) sums periods, so
+/// they can't drift out of sync with each other.
+fn sum_periods(entries: &[Entry], now: LocalDT) -> Result<BTreeMap<NaiveDate, TimeDelta>, String> {
+    let mut by_day: BTreeMap<NaiveDate, TimeDelta> = BTreeMap::new();
+
+    let mut current_slot_begin = None;
+    for entry in entries {
+        match entry {
+            Entry::Begin { datetime, .. } => current_slot_begin = Some(*datetime),
+            Entry::End { datetime, .. } => {
+                if let Some(begin) = current_slot_begin {
+                    *by_day.entry(begin.date_naive()).or_insert_with(TimeDelta::zero) +=
+                        *datetime - begin;
+                    current_slot_begin = None;
+                } else {
+                    return Err(format!(
+                        "Corrupted database. End at {} without previous period begin.",
+                        datetime.format(DT_FMT)
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(begin) = current_slot_begin {
+        *by_day.entry(begin.date_naive()).or_insert_with(TimeDelta::zero) += now - begin;
+    }
+
+    Ok(by_day)
+}
+
+fn total_time(by_day: &BTreeMap<NaiveDate, TimeDelta>) -> TimeDelta {
+    by_day.values().fold(TimeDelta::zero(), |acc, time| acc + *time)
+}
+
+/// Merge several sheets' per-day totals into one, summing days they share.
+fn merge_day_totals(
+    by_day_per_sheet: Vec<BTreeMap<NaiveDate, TimeDelta>>,
+) -> BTreeMap<NaiveDate, TimeDelta> {
+    let mut merged: BTreeMap<NaiveDate, TimeDelta> = BTreeMap::new();
+    for by_day in by_day_per_sheet {
+        for (day, time) in by_day {
+            *merged.entry(day).or_insert_with(TimeDelta::zero) += time;
+        }
+    }
+    merged
+}
+
+fn day_start(dt: LocalDT) -> LocalDT {
+    dt.with_time(NaiveTime::from_hms_opt(0, 0, 0).expect("is valid"))
+        .unwrap()
+}
+
+/// Convert a bare date (as parsed from a `--from`/`--to` flag) to midnight in
+/// the local timezone.
+fn localize_date(date: NaiveDate) -> Result<LocalDT, String> {
+    let naive = date.and_hms_opt(0, 0, 0).expect("midnight is valid");
+
+    chrono::Local.from_local_datetime(&naive).single().ok_or_else(|| {
+        format!("{} is ambiguous or does not exist in the local timezone.", date.format(DATE_FMT))
+    })
+}
+
+fn print_total(label: &str, time: TimeDelta) {
+    println!(
+        "{label}: {:2}:{:02}",
+        time.num_hours(),
+        time.num_minutes() - time.num_hours() * 60
+    );
+}
+
+fn print_day_totals(label: &str, by_day: &BTreeMap<NaiveDate, TimeDelta>) {
+    for (day, time) in by_day {
+        print_total(&day.format(DATE_FMT).to_string(), *time);
+    }
+    print_total(label, total_time(by_day));
+}
+
+fn entry_kind(entry: &Entry) -> &'static str {
+    match entry {
+        Entry::Begin { .. } => "begin",
+        Entry::End { .. } => "end",
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn export_csv(entries: &[Entry]) {
+    println!("id,kind,datetime,sheet,note");
+    for entry in entries {
+        println!(
+            "{},{},{},{},{}",
+            entry.id(),
+            entry_kind(entry),
+            entry.datetime().to_rfc3339(),
+            csv_field(entry.sheet()),
+            csv_field(entry.note().unwrap_or(""))
+        );
+    }
+}
+
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn export_json(entries: &[Entry]) {
+    println!("[");
+    for (index, entry) in entries.iter().enumerate() {
+        let comma = if index + 1 < entries.len() { "," } else { "" };
+        let note = match entry.note() {
+            Some(note) => format!("\"{}\"", json_escape(note)),
+            None => "null".to_string(),
+        };
+        println!(
+            "  {{\"id\": {}, \"kind\": \"{}\", \"datetime\": \"{}\", \"sheet\": \"{}\", \"note\": {note}}}{comma}",
+            entry.id(),
+            entry_kind(entry),
+            entry.datetime().to_rfc3339(),
+            json_escape(entry.sheet())
+        );
+    }
+    println!("]");
+}
+
 #[derive(clap::Parser, Debug)]
+struct Cli {
+    /// Pretend it is this time instead of now, e.g. to record a begin/end
+    /// you forgot to clock at the time. Format: "HH:MM dd.mm.yy".
+    #[arg(long, global = true)]
+    at: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
 enum Command {
     Begin,
-    End,
-    Show,
-    Today,
+    End {
+        /// Attach a note to the period being ended.
+        #[arg(long)]
+        note: Option<String>,
+    },
+    Show {
+        /// Only show entries on this sheet. If omitted, all sheets are shown,
+        /// grouped by sheet.
+        #[arg(long)]
+        sheet: Option<String>,
+    },
+    Today {
+        /// Only total up this sheet. If omitted, every sheet is totalled
+        /// separately alongside the grand total.
+        #[arg(long)]
+        sheet: Option<String>,
+    },
+    /// Total time spent this week (Monday to Sunday), per day.
+    Week {
+        /// Only total up this sheet. If omitted, all sheets are combined.
+        #[arg(long)]
+        sheet: Option<String>,
+    },
+    /// Total time spent this month, per day.
+    Month {
+        /// Only total up this sheet. If omitted, all sheets are combined.
+        #[arg(long)]
+        sheet: Option<String>,
+    },
+    /// Total time spent in an arbitrary date range, per day.
+    Range {
+        /// Start date, inclusive, as "dd.mm.yy".
+        #[arg(long)]
+        from: String,
+        /// End date, inclusive, as "dd.mm.yy".
+        #[arg(long)]
+        to: String,
+        /// Only total up this sheet. If omitted, all sheets are combined.
+        #[arg(long)]
+        sheet: Option<String>,
+    },
+    /// Switch the active sheet that `begin`/`end` tag new entries with,
+    /// creating it implicitly if it hasn't been used before.
+    Sheet {
+        name: String,
+    },
+    /// Attach a note to the currently open period on the active sheet.
+    Note {
+        text: String,
+    },
+    /// Rewrite an entry's datetime and/or note. The id is shown by `show`.
+    Edit {
+        id: i64,
+        /// New datetime, parsed as "HH:MM dd.mm.yy".
+        #[arg(long)]
+        datetime: Option<String>,
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Delete a single entry. The id is shown by `show`.
+    Delete {
+        id: i64,
+    },
+    /// Export all entries, optionally restricted to a date range, to stdout.
+    Export {
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+        /// Start date, inclusive, as "dd.mm.yy". Must be given together with --to.
+        #[arg(long)]
+        from: Option<String>,
+        /// End date, inclusive, as "dd.mm.yy". Must be given together with --from.
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Migrate entries in from another time tracker's database.
+    Import {
+        /// Path to the source database.
+        #[arg(long)]
+        from: PathBuf,
+        #[arg(long, value_enum)]
+        format: ImportFormat,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum ImportFormat {
+    Timetrap,
 }
 
 enum Entry {
-    Begin(LocalDT),
-    End(LocalDT),
+    Begin {
+        id: i64,
+        datetime: LocalDT,
+        sheet: String,
+        note: Option<String>,
+    },
+    End {
+        id: i64,
+        datetime: LocalDT,
+        sheet: String,
+        note: Option<String>,
+    },
+}
+
+impl Entry {
+    fn id(&self) -> i64 {
+        match self {
+            Entry::Begin { id, .. } => *id,
+            Entry::End { id, .. } => *id,
+        }
+    }
+
+    fn datetime(&self) -> LocalDT {
+        match self {
+            Entry::Begin { datetime, .. } => *datetime,
+            Entry::End { datetime, .. } => *datetime,
+        }
+    }
+
+    fn sheet(&self) -> &str {
+        match self {
+            Entry::Begin { sheet, .. } => sheet,
+            Entry::End { sheet, .. } => sheet,
+        }
+    }
+
+    fn note(&self) -> Option<&str> {
+        match self {
+            Entry::Begin { note, .. } => note.as_deref(),
+            Entry::End { note, .. } => note.as_deref(),
+        }
+    }
 }
 
 fn import_datetime(x: i64) -> LocalDT {
@@ -258,8 +1222,34 @@ fn import_datetime(x: i64) -> LocalDT {
         .with_timezone(&chrono::Local)
 }
 
+/// Timetrap stores timestamps like "2013-01-01 09:00:00 -0500".
+const TIMETRAP_DT_FMT: &'static str = "%Y-%m-%d %H:%M:%S %z";
+
+fn parse_timetrap_dt(raw: &str) -> Result<LocalDT, String> {
+    chrono::DateTime::parse_from_str(raw, TIMETRAP_DT_FMT)
+        .map(|dt| dt.with_timezone(&chrono::Local))
+        .map_err(|err| format!("Could not parse timetrap datetime \"{raw}\" as \"{TIMETRAP_DT_FMT}\": {err}"))
+}
+
+/// Parse a `--at` override in [`DT_FMT`] into a local datetime.
+fn parse_at(raw: &str) -> Result<LocalDT, String> {
+    let naive = NaiveDateTime::parse_from_str(raw, DT_FMT)
+        .map_err(|err| format!("Could not parse --at \"{raw}\" as \"{DT_FMT}\": {err}"))?;
+
+    chrono::Local
+        .from_local_datetime(&naive)
+        .single()
+        .ok_or_else(|| {
+            format!("\"{raw}\" is ambiguous or does not exist in the local timezone.")
+        })
+}
+
 impl Entry {
     pub fn from_db_row(row: &Row) -> Result<Entry, String> {
+        let id: i64 = row
+            .get("id")
+            .map_err(|err| format!("Could not get id from row: {err}"))?;
+
         let timestamp: LocalDT = row
             .get("datetime")
             .map(import_datetime)
@@ -268,35 +1258,67 @@ impl Entry {
         let kind: i64 = row
             .get("kind")
             .map_err(|err| format!("Could not get datetime from row: {err}"))?;
+        let sheet: String = row
+            .get("sheet")
+            .map_err(|err| format!("Could not get sheet from row: {err}"))?;
+        let note: Option<String> = row
+            .get("note")
+            .map_err(|err| format!("Could not get note from row: {err}"))?;
         Ok(match kind {
-            0 => Entry::Begin(timestamp),
-            1 => Entry::End(timestamp),
+            0 => Entry::Begin {
+                id,
+                datetime: timestamp,
+                sheet,
+                note,
+            },
+            1 => Entry::End {
+                id,
+                datetime: timestamp,
+                sheet,
+                note,
+            },
             _ => {
-                return match row.get::<_, i64>("id") {
-                    Ok(id) => Err(format!(
-                        "Corrupted database contents: Found entry kind {kind} at id {id}. Expected 0 (Begin) or 1 (End)."
-                    )),
-                    Err(other_err) => Err(format!(
-                        "Corrupted database contents: Found entry kind {kind}. Expected 0 (Begin) or 1 (End). Another error occurred when trying to get the corresponding entry id: {other_err}."
-                    )),
-                };
+                return Err(format!(
+                    "Corrupted database contents: Found entry kind {kind} at id {id}. Expected 0 (Begin) or 1 (End)."
+                ));
             }
         })
     }
 }
 
 fn main() {
-    let mut app = App::try_init().unwrap_or_else(|err| {
+    let args = Cli::parse();
+
+    let now = args
+        .at
+        .as_deref()
+        .map(parse_at)
+        .transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        })
+        .unwrap_or_else(chrono::Local::now);
+
+    let mut app = App::try_init(now).unwrap_or_else(|err| {
         eprintln!("Could not initialize application: {err}");
         std::process::exit(1);
     });
 
-    let args = Command::parse();
-    match args {
+    match args.command {
         Command::Begin => app.add_begin(),
-        Command::End => app.add_end(),
-        Command::Show => app.show(),
-        Command::Today => app.today(),
+        Command::End { note } => app.add_end(note),
+        Command::Show { sheet } => app.show(sheet.as_deref()),
+        Command::Today { sheet } => app.today(sheet.as_deref()),
+        Command::Week { sheet } => app.week(sheet.as_deref()),
+        Command::Month { sheet } => app.month(sheet.as_deref()),
+        Command::Range { from, to, sheet } => app.range(&from, &to, sheet.as_deref()),
+        Command::Sheet { name } => app.switch_sheet(&name),
+        Command::Note { text } => app.annotate_open_period(&text),
+        Command::Edit { id, datetime, note } => app.edit(id, datetime, note),
+        Command::Delete { id } => app.delete(id),
+        Command::Export { format, from, to } => app.export(format, from.as_deref(), to.as_deref()),
+        Command::Import { from, format } => app.import(&from, format),
     }
     .unwrap_or_else(|err| {
         eprintln!("{err}");